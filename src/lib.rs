@@ -6,51 +6,91 @@ use wasm_bindgen::prelude::*;
 use plotters::prelude::*;
 use plotters_canvas::CanvasBackend;
 use web_sys::HtmlCanvasElement;
-use base64::{Engine as _, prelude::BASE64_STANDARD as b64};
+use base64::{Engine as _, prelude::BASE64_STANDARD as b64, prelude::BASE64_URL_SAFE as b64url};
 use base16ct::mixed::decode_vec as hexdec;
 use nohash_hasher::IntMap;
 
-/*
-TODO:
-- If there is a consecutive chain of spikes (ex. periods 17,18,19 all 'spike'), then
-  only place a triangle on the largest spike in that chain.
+/// A decode failure, tagged with the format that produced it.
+///
+/// Carrying the format name lets `Display` prefix every decoding failure with
+/// it (e.g. `"BASE64: Invalid input length"`), so the message says which
+/// decoder rejected the input. An unrecognised format is reported as
+/// `"Invalid encoding format (<fmt>)"`.
+#[derive(Debug)]
+pub enum DecodeError {
+    Hex(String),
+    Base64(String),
+    Base64Url(String),
+    Decimal(String),
+    UnknownFormat(String),
+}
 
-- If the period is very low (2, 3, 4), spikes are so frequent that they don't deviate from the mean
-  and therefore our standard deviation test can't find them. Find some solution to fix this.
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::Hex(e) => write!(f, "HEX: {e}"),
+            DecodeError::Base64(e) => write!(f, "BASE64: {e}"),
+            DecodeError::Base64Url(e) => write!(f, "BASE64URL: {e}"),
+            DecodeError::Decimal(e) => write!(f, "DECIMAL: {e}"),
+            DecodeError::UnknownFormat(fmt) => write!(f, "Invalid encoding format ({fmt})"),
+        }
+    }
+}
+
+impl From<DecodeError> for JsValue {
+    fn from(e: DecodeError) -> Self {
+        return e.to_string().into();
+    }
+}
 
-- The way error handling currently works can leave some vague error messages. For instance, 
-  providing invalid base64 displays the error "Invalid input length" which is pretty unclear. 
-  Something like "Base64: Invalid input length" would be much better.
-*/
+/// Decode `input` into raw bytes according to `fmt`.
+///
+/// `fmt` - one of "BYTES" (the input string's raw bytes), "HEX", "BASE64",
+/// "BASE64URL", or "DECIMAL" (a space/comma-separated list of byte values).
+/// Supporting a new encoding is one extra match arm. Note that "UTF8" is *not*
+/// handled here: it keys on full Unicode codepoints rather than bytes, so
+/// `transcribe` resolves it before reaching this byte-returning decoder.
+fn decode(fmt: &str, input: &str) -> Result<Vec<u8>, DecodeError> {
+    return match fmt {
+        "BYTES" => Ok(input.as_bytes().to_vec()),
+        "HEX" => hexdec(input).map_err(|e| DecodeError::Hex(e.to_string())),
+        "BASE64" => b64.decode(input).map_err(|e| DecodeError::Base64(e.to_string())),
+        "BASE64URL" => b64url.decode(input).map_err(|e| DecodeError::Base64Url(e.to_string())),
+        "DECIMAL" => input.split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|s| !s.is_empty())
+            .map(|s| s.parse::<u8>().map_err(|e| DecodeError::Decimal(e.to_string())))
+            .collect(),
+        _ => Err(DecodeError::UnknownFormat(fmt.to_string())),
+    };
+}
 
-/// Take input, decode it as UTF8/hex/base64-encoded data,
+/// Take input, decode it according to `fmt`,
 /// then return an alphabetic transcription of the data.
-/// 
+///
 /// `input` - The data to be transcribed.
-/// `fmt` - The format of the data. Must be "UTF8", "HEX", or "BASE64".
-/// 
-/// 
+/// `fmt` - The format of the data: "UTF8" (codepoint-based) or any byte format
+/// accepted by `decode`.
+///
+///
 /// ```rust
-/// 
+///
 /// let input = "abbacddabcabddacdb";
 /// assert_eq!(transcribe(&inp, &"UTF8").unwrap(),vec![0, 1, 1, 0, 2, 3, 3, 0, 1, 2, 0, 1, 3, 3, 0, 2, 3, 1]);
 /// ```
 #[wasm_bindgen]
 pub fn transcribe(input: &str, fmt: &str) -> Result<Vec<u32>, JsValue> {
-    let data: Vec<char> = match fmt {
-        "UTF8" => input.chars().collect(),
-        "HEX" => hexdec(input).map_err(|e| e.to_string())?
-                    .iter().map(|d| *d as char).collect(),
-        "BASE64" => b64.decode(input).map_err(|e| e.to_string())?
-                        .iter().map(|d| *d as char).collect(),
-        _ => return Err("Invalid encoding format (???)".into())
+    // UTF8 keys on full Unicode codepoints (as the baseline did); every other
+    // format is a byte stream handled by `decode`.
+    let data: Vec<u32> = match fmt {
+        "UTF8" => input.chars().map(|c| c as u32).collect(),
+        _ => decode(fmt, input)?.into_iter().map(|b| b as u32).collect(),
     };
 
     let mut counts: IntMap<u32,u32> = IntMap::default();
     return Ok(data.into_iter()
     .map(|x| {
         let l = counts.len() as u32;
-        *counts.entry(x as u32).or_insert(l) 
+        *counts.entry(x).or_insert(l)
         }).collect());
 }
 
@@ -64,12 +104,24 @@ pub fn transcribe(input: &str, fmt: &str) -> Result<Vec<u32>, JsValue> {
 /// 
 /// `cache` - A list of previously-computed IOC results so this function doesn't need to recalculate them.
 #[wasm_bindgen]
-pub fn analyze(canvas: HtmlCanvasElement, data: Vec<u32>, range: usize, mut cache: Vec<f32>) -> Result<Vec<f32>,  JsValue> {
-    // If cache holds enough data to graph, use it!
+pub fn analyze(canvas: HtmlCanvasElement, data: Vec<u32>, range: usize, cache: Vec<f32>, threshold: f32) -> Result<Vec<f32>,  JsValue> {
+    let cache = ioc_curve(&data, range, cache)?;
+    // Only the first `range-1` entries are relevant to this plot, even if the
+    // cache carries more from a previous, wider analysis.
+    let plotted: Vec<f32> = cache.clone().into_iter().take(range-1).collect();
+    plot(canvas,&plotted,threshold)?;
+    return Ok(cache);
+}
+
+/// Compute the column-transposition IoC curve, extending `cache` as needed.
+///
+/// This is the shared compute path behind both `analyze` (canvas) and
+/// `analyze_to_svg` (headless). It returns the full cache so callers can keep
+/// reusing it; each caller decides how much of it to plot.
+fn ioc_curve(data: &[u32], range: usize, mut cache: Vec<f32>) -> Result<Vec<f32>, JsValue> {
+    // If cache already holds enough data, use it as-is!
     if range <= cache.len() {
-        let cached: Vec<f32> = cache.clone().into_iter().take(range-1).collect();
-        plot(canvas,&cached)?;
-        return Ok(cache) 
+        return Ok(cache);
     } // Otherwise we'll need to extend it down below.
 
     // Don't analyze extremely short data.
@@ -99,7 +151,7 @@ pub fn analyze(canvas: HtmlCanvasElement, data: Vec<u32>, range: usize, mut cach
             // For the amount of items that are going to be in that row...
             for k in 0..amt{
                 // Add the character to the 'counts' array.
-                counts[data[j + k*i] as usize] += 1;    
+                counts[data[j + k*i] as usize] += 1;
             }
             // Now we've counted all the characters for this column.
             // We'll now calculate the IOC with this data.
@@ -121,36 +173,199 @@ pub fn analyze(canvas: HtmlCanvasElement, data: Vec<u32>, range: usize, mut cach
 
     }
 
+    return Ok(cache);
+}
+
+/// A headless analysis result: the IoC cache plus the chart rendered as SVG.
+///
+/// Returned by `analyze_to_svg` so the headless path is reachable from JS/Node
+/// (wasm-bindgen can't marshal a bare tuple), mirroring how `KeyFit` exposes its
+/// fields through getters.
+#[wasm_bindgen]
+pub struct SvgReport {
+    iocs: Vec<f32>,
+    svg: String,
+}
+
+#[wasm_bindgen]
+impl SvgReport {
+    #[wasm_bindgen(getter)]
+    pub fn iocs(&self) -> Vec<f32> {
+        return self.iocs.clone();
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn svg(&self) -> String {
+        return self.svg.clone();
+    }
+}
+
+/// Headless sibling to `analyze`: run the Kullback test and render the chart to
+/// an SVG `String` instead of a canvas.
+///
+/// This reuses the exact same IoC/caching path (`ioc_curve`) and mesh/line/
+/// triangle drawing logic (`draw_chart`) as `analyze`, but targets plotters'
+/// string-buffer backend — so the analysis can run server-side, in Node, or in
+/// a snapshot test where no `HtmlCanvasElement` exists. It returns the extended
+/// cache alongside the rendered SVG.
+///
+/// `data` - The data to be analyzed. Must be transcribed with `transcribe()` first.
+///
+/// `range` - The maximum period to test.
+///
+/// `cache` - A list of previously-computed IOC results so this function doesn't need to recalculate them.
+///
+/// `threshold` - the modified z-score above which a point is flagged as a spike.
+#[wasm_bindgen]
+pub fn analyze_to_svg(data: Vec<u32>, range: usize, cache: Vec<f32>, threshold: f32) -> Result<SvgReport, JsValue> {
+    // A curve needs at least one point; otherwise `draw_chart`'s min/max search
+    // would panic on an empty slice.
+    if range < 2 {return Err("Range is too small (2 minimum)".into())}
+
+    let cache = ioc_curve(&data, range, cache)?;
+    let plotted: Vec<f32> = cache.clone().into_iter().take(range-1).collect();
+
+    let mut buf = String::new();
+    {
+        let root = SVGBackend::with_string(&mut buf, (640, 480)).into_drawing_area();
+        draw_chart(&root, &plotted, threshold)?;
+        root.present().map_err(|e| e.to_string())?;
+    }
+    return Ok(SvgReport { iocs: cache, svg: buf });
+}
+
+
+/// Analyze input with a shift-autocorrelation coincidence test, then plot it.
+///
+/// Where `analyze` measures within-column uniformity (and so goes blind for very
+/// short periods such as 2–4), this correlates the data with delayed copies of
+/// itself — the same trick audio-pitch detectors use to find a fundamental
+/// period. For each shift `s` it computes
+///
+/// κ(s) = (1 / (N − s)) · Σ_{i=0}^{N−s−1} [data[i] == data[i + s]]
+///
+/// i.e. the fraction of positions where a symbol matches the one `s` places
+/// later. For a key of length `L` this spikes well above the random baseline
+/// (≈ 1 / alphabet_size) at every multiple of `L`, so the smallest
+/// strongly-spiking shift reveals the period even when it is 2–4.
+///
+/// `canvas` - An HtmlCanvasElement that the results will be graphed to.
+///
+/// `data` - The data to be analyzed. Must be transcribed with `transcribe()` first.
+///
+/// `range` - The maximum shift to test.
+///
+/// `cache` - A list of previously-computed coincidence results so this function doesn't need to recalculate them.
+#[wasm_bindgen]
+pub fn autocorrelate(canvas: HtmlCanvasElement, data: Vec<u32>, range: usize, mut cache: Vec<f32>, threshold: f32) -> Result<Vec<f32>,  JsValue> {
+    // A curve needs at least one point (shift 1); otherwise there is nothing to
+    // plot and the min/max search in `plot` would panic on an empty slice.
+    if range < 2 {return Err("Range is too small (2 minimum)".into())}
+
+    // If cache holds enough data to graph, use it!
+    if range <= cache.len() {
+        let cached: Vec<f32> = cache.clone().into_iter().take(range-1).collect();
+        plot(canvas,&cached,threshold)?;
+        return Ok(cache)
+    } // Otherwise we'll need to extend it down below.
+
+    // Don't analyze extremely short data.
+    if data.len() < 4 {return Err("Length of input is too short (4 chars minimum)".into())}
+
+    // Range cannot be greater than len(data)/2, because correlating less than two full blocks is useless.
+    if range > data.len()/2 {return Err("Range is too large. Please decrease.".into())}
+
+    utils::set_panic_hook();
+
+    // Extend cache to hold everything we need
+    cache.reserve(range-cache.len());
+
+    let n = data.len();
+    // For each shift value we need to calculate...
+    for s in (cache.len()+1)..range {
+        // Count the positions whose symbol equals the one `s` places later.
+        let mut matches: usize = 0;
+        for i in 0..(n-s) {
+            matches += (data[i] == data[i+s]) as usize;
+        }
+        // Add the coincidence fraction to the cache.
+        cache.push(matches as f32 / (n - s) as f32);
+    }
+
     // Plot it!
-    plot(canvas,&cache)?;
+    plot(canvas,&cache,threshold)?;
 
     return Ok(cache);
 }
 
 
+/// Compute the median of a slice of scores.
+///
+/// The slice is copied and sorted (with `total_cmp`, so NaNs don't poison the
+/// ordering) before the middle element(s) are taken, leaving the caller's data
+/// untouched.
+fn median(xs: &[f32]) -> f32 {
+    let mut sorted = xs.to_owned();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let mid = sorted.len() / 2;
+    if sorted.len() % 2 == 0 {
+        (sorted[mid-1] + sorted[mid]) / 2.0
+    } else {
+        sorted[mid]
+    }
+}
+
 /// Plot a list of IoC results given an HTML canvas.
-/// 
+///
 /// `canvas` - an HTML Canvas element that will have the results graphed to it.
-/// 
+///
 /// `iocs` - the list of IoC results that should be plotted.
-fn plot(canvas: HtmlCanvasElement, iocs: &[f32]) -> Result<(), JsValue>{
+///
+/// `threshold` - the modified z-score above which a point is flagged as a spike.
+/// A value of ≈3.5 is the usual default; lower it to flag more peaks.
+fn plot(canvas: HtmlCanvasElement, iocs: &[f32], threshold: f32) -> Result<(), JsValue>{
+    let root = CanvasBackend::with_canvas_object(canvas)
+    .ok_or("Failed to create canvas")?.into_drawing_area();
+    draw_chart(&root, iocs, threshold)?;
+    return Ok(());
+}
+
+/// Render the IoC line, axes and spike markers onto a drawing area.
+///
+/// Backend-agnostic so both `plot` (canvas) and `analyze_to_svg` (string
+/// buffer) share the same mesh/line/triangle logic; see `threshold` in `plot`.
+fn draw_chart<DB>(root: &DrawingArea<DB, plotters::coord::Shift>, iocs: &[f32], threshold: f32) -> Result<(), JsValue>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: std::error::Error + Send + Sync + 'static,
+{
     let range: usize = iocs.len()+1;
 
-    // Calculate max/min/mean/standard deviation.
+    // Calculate max/min for the plot bounds.
     let min = *iocs.iter().min_by(|x,y| x.total_cmp(y)).unwrap();
     let max = *iocs.iter().max_by(|x,y| x.total_cmp(y)).unwrap();
-    let mean = iocs.iter().sum::<f32>() / iocs.len() as f32;
-    let stdev = (iocs.iter().map(|x| (mean-x).powi(2)).sum::<f32>() / iocs.len() as f32).sqrt();
 
-    // Find all IoC calculations 1.5 standard deviations from the mean
-    let spikes: Vec<usize> = iocs.iter().enumerate().filter(|&(_, x)| ((x-mean)/stdev > 1.5)).map(|(i, _)| i).collect();
+    // Find spikes with a robust median/MAD detector. Unlike the mean and stdev
+    // (both inflated when spikes are frequent, as with very low periods), the
+    // median and MAD are unaffected by a large fraction of outliers.
+    let m = median(iocs);
+    let mad = median(&iocs.iter().map(|x| (x-m).abs()).collect::<Vec<f32>>());
+    let spikes: Vec<usize> = iocs.iter().enumerate().filter(|&(i, x)| {
+        // A zero MAD means the data is essentially flat — nothing to flag.
+        if mad == 0.0 {return false}
+        // Modified z-score test.
+        if 0.6745 * (x - m) / mad <= threshold {return false}
+        // Gate on being a strict local maximum so a run of elevated periods
+        // (e.g. periods 17, 18 and 19 all spiking) collapses to its tallest member.
+        let left = i == 0 || *x > iocs[i-1];
+        let right = i == iocs.len()-1 || *x > iocs[i+1];
+        left && right
+    }).map(|(i, _)| i).collect();
 
     // Begin plotting!
-    let root = CanvasBackend::with_canvas_object(canvas)
-    .ok_or("Failed to create canvas")?.into_drawing_area();
-    root.fill(&WHITE).unwrap();
+    root.fill(&WHITE).map_err(|e| e.to_string())?;
 
-    let mut chart_builder = ChartBuilder::on(&root);
+    let mut chart_builder = ChartBuilder::on(root);
     chart_builder.margin(5).margin_top(15).set_left_and_bottom_label_area_size(35);
     let mut chart_context = chart_builder
         .build_cartesian_2d(0..range, (min*0.95)..(max*1.05))
@@ -192,3 +407,131 @@ fn plot(canvas: HtmlCanvasElement, iocs: &[f32]) -> Result<(), JsValue>{
     return Ok(());
 }
 
+
+
+/// The recovered Vigenère key: the best Caesar shift for each column together
+/// with that column's chi-squared score. A high score means a poor fit, so
+/// callers can highlight low-confidence columns.
+#[wasm_bindgen]
+pub struct KeyFit {
+    shifts: Vec<u32>,
+    scores: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl KeyFit {
+    #[wasm_bindgen(getter)]
+    pub fn shifts(&self) -> Vec<u32> {
+        return self.shifts.clone();
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn scores(&self) -> Vec<f32> {
+        return self.scores.clone();
+    }
+}
+
+/// Recover the per-column Caesar shifts (the Vigenère key) once the period is known.
+///
+/// Splits `data` into `l` columns (position `i` goes to column `i mod l`) and,
+/// for each column, picks the shift `g` in `0..alphabet_size` that minimizes the
+/// chi-squared statistic Σ_c (observed_c − expected_shifted_c)² / expected_shifted_c
+/// between the column's shift-corrected histogram and the reference distribution.
+/// This turns the key-length detector into an end-to-end Vigenère solver.
+///
+/// `data` - The data to be solved. Must be transcribed with `transcribe()` first.
+///
+/// `l` - The chosen key period.
+///
+/// `expected` - The expected letter-frequency vector for the plaintext language,
+/// indexed by the same symbols `transcribe()` produces.
+#[wasm_bindgen]
+pub fn solve_columns(data: Vec<u32>, l: usize, expected: Vec<f32>) -> Result<KeyFit, JsValue> {
+    if data.is_empty() {return Err("Length of input is too short".into())}
+    if l == 0 {return Err("Period must be at least 1".into())}
+
+    let alphabet = *data.iter().max().unwrap() as usize + 1;
+    if expected.len() < alphabet {return Err("Expected-frequency vector is smaller than the alphabet".into())}
+
+    let mut shifts = vec![0u32; l];
+    let mut scores = vec![0f32; l];
+
+    // Per-column frequency counts, reused across columns.
+    let mut counts = vec![0usize; alphabet];
+
+    for col in 0..l {
+        // Count the symbols landing in this column (positions col, col+l, col+2l, ...).
+        let mut col_len = 0usize;
+        let mut i = col;
+        while i < data.len() {
+            counts[data[i] as usize] += 1;
+            col_len += 1;
+            i += l;
+        }
+        let col_len = col_len as f32;
+
+        // An empty column carries no evidence: every expected count is zero, so
+        // chi-squared would collapse to 0 and masquerade as a perfect fit. Report
+        // it as worst-confidence instead so callers can highlight it.
+        if col_len == 0.0 {
+            shifts[col] = 0;
+            scores[col] = f32::INFINITY;
+            continue;
+        }
+
+        // Try every shift and keep the one with the smallest chi-squared score.
+        let mut best_shift = 0u32;
+        let mut best_score = f32::INFINITY;
+        for g in 0..alphabet {
+            let mut chi2 = 0f32;
+            for c in 0..alphabet {
+                // Under shift g, cipher symbol c decrypts to plaintext (c - g) mod alphabet.
+                let p = (c + alphabet - g) % alphabet;
+                let e = expected[p] * col_len;
+                if e == 0.0 {continue} // Avoid dividing by an impossible expected count.
+                let o = counts[c] as f32;
+                chi2 += (o - e).powi(2) / e;
+            }
+            if chi2 < best_score {
+                best_score = chi2;
+                best_shift = g as u32;
+            }
+        }
+        shifts[col] = best_shift;
+        scores[col] = best_score;
+
+        // Clear counts for the next column.
+        for h in counts.iter_mut() {*h = 0}
+    }
+
+    return Ok(KeyFit { shifts, scores });
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = "abbacddabcabddacdbabbacddabcabddacdb";
+    const GOLDEN: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/analyze_to_svg.svg");
+
+    // Snapshot the headless render on fixed input. The golden file is created on
+    // first run (or when UPDATE_GOLDEN is set) and compared byte-for-byte
+    // thereafter, so any drift in the rendered SVG fails the test.
+    #[test]
+    fn analyze_to_svg_matches_golden() {
+        let data = transcribe(FIXTURE, "UTF8").unwrap();
+        let report = analyze_to_svg(data, 6, Vec::new(), 3.5).unwrap();
+
+        // One IoC per tested period (1..range).
+        assert_eq!(report.iocs().len(), 5);
+
+        let svg = report.svg();
+        if std::env::var("UPDATE_GOLDEN").is_ok() || !std::path::Path::new(GOLDEN).exists() {
+            std::fs::create_dir_all(std::path::Path::new(GOLDEN).parent().unwrap()).unwrap();
+            std::fs::write(GOLDEN, &svg).unwrap();
+        }
+        let golden = std::fs::read_to_string(GOLDEN).unwrap();
+        assert_eq!(svg, golden, "rendered SVG drifted from golden; re-run with UPDATE_GOLDEN=1 to refresh");
+    }
+}